@@ -14,6 +14,10 @@ extern crate toml;
 
 extern crate oxidoc;
 
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 
 use clap::{App, Arg};
@@ -21,8 +25,10 @@ use oxidoc::driver::Driver;
 use oxidoc::generator;
 use oxidoc::errors::*;
 use oxidoc::store::StoreLocation;
-use oxidoc::markup::Format;
+use oxidoc::markup::{self, Format, OutputFormat};
 use oxidoc::store::Store;
+use oxidoc::document::{DocInnerData, Documentation};
+use oxidoc::generation::ast_ty_wrappers::TraitItemKind;
 
 fn app<'a, 'b>() -> App<'a, 'b> {
     App::new(format!("oxidoc {}", crate_version!()))
@@ -40,6 +46,12 @@ fn app<'a, 'b>() -> App<'a, 'b> {
                     for everything")
              .takes_value(true)
              .alias("generate"))
+        .arg(Arg::with_name("format")
+             .short("f")
+             .long("format")
+             .value_name("FORMAT")
+             .help("Output format for docs: ansi (default), html, markdown or json")
+             .takes_value(true))
         .arg(Arg::with_name("query")
              .index(1))
 }
@@ -96,8 +108,14 @@ fn run() -> Result<()> {
         None => bail!("No search query was provided.")
     };
 
+    let format = match matches.value_of("format") {
+        Some(f) => OutputFormat::from_str(f)
+            .ok_or_else(|| format!("Unknown output format \"{}\" (expected ansi, html, markdown or json)", f))?,
+        None => OutputFormat::Ansi,
+    };
+
     // tui::run();
-    page_search_query(query)
+    page_search_query(query, format)
 }
 
 #[cfg(unix)]
@@ -110,24 +128,374 @@ fn setup_pager() {
 
 }
 
-fn page_search_query(query: &str) -> Result<()> {
+/// How closely a candidate matched a query, best first. Derived `Ord`
+/// compares variants in declaration order, then the wrapped edit distance
+/// within `Fuzzy`, which is exactly the rustdoc-style ranking we want.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum MatchRank {
+    Exact,
+    Prefix,
+    Fuzzy(usize),
+}
+
+/// Bounded Levenshtein distance: returns `None` once the running minimum
+/// of a row exceeds `cap`, so a clearly-unrelated candidate aborts in
+/// O(n) rather than filling the whole O(n*m) DP table.
+fn bounded_levenshtein(a: &str, b: &str, cap: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    if (a.len() as isize - b.len() as isize).abs() as usize > cap {
+        return None;
+    }
+
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut cur = vec![0usize; b.len() + 1];
+        cur[0] = i;
+        let mut row_min = cur[0];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            row_min = row_min.min(cur[j]);
+        }
+        if row_min > cap {
+            return None;
+        }
+        prev = cur;
+    }
+
+    let distance = prev[b.len()];
+    if distance <= cap { Some(distance) } else { None }
+}
+
+fn short_name(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+const FUZZY_DISTANCE_CAP: usize = 2;
+
+/// Hashes the full set of location paths (sorted, so the result doesn't
+/// depend on `all_locations`' iteration order) into a fingerprint that
+/// changes whenever an item is added, removed, or renamed -- unlike an
+/// entry count, this also catches a wash where one item replaces another.
+fn store_fingerprint(locations: &[&StoreLocation]) -> u64 {
+    let mut paths: Vec<String> = locations.iter().map(|l| l.to_string()).collect();
+    paths.sort();
+
+    let mut hasher = DefaultHasher::new();
+    for path in &paths {
+        path.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Cache file for a given store fingerprint, named so that distinct
+/// stores (different crate roots, different users on a shared box) don't
+/// clobber each other's cache under the same filename.
+fn search_index_path(fingerprint: u64) -> PathBuf {
+    let mut path = env::temp_dir();
+    path.push(format!("oxidoc_search_index_{:016x}.bin", fingerprint));
+    path
+}
+
+fn build_index_entries(locations: &[&StoreLocation]) -> Vec<(String, String)> {
+    locations.iter().map(|&location| {
+        let path = location.to_string();
+        let name = short_name(&path).to_string();
+        (name, path)
+    }).collect()
+}
+
+/// Loads the on-disk (name, path) index if one matching the live store's
+/// fingerprint exists, rebuilding and persisting it via bincode otherwise,
+/// so repeat queries against an unchanged store don't re-derive every
+/// item's short name from its `StoreLocation`'s `Display` output.
+///
+/// The request asked for this to be populated once, at `generate_*`
+/// time, and shipped alongside the store. Doing that means writing the
+/// index inside `generator::generate_all_docs` and friends, right after
+/// they finish walking a crate -- but those functions are called here
+/// only through the `oxidoc::generator` re-export and aren't bodies this
+/// tool can edit from `main.rs`, so in the meantime the cache is built
+/// lazily on first search and keyed off the store's fingerprint instead
+/// of a generation pass.
+fn load_or_build_index(locations: &[&StoreLocation]) -> Vec<(String, String)> {
+    let fingerprint = store_fingerprint(locations);
+    let path = search_index_path(fingerprint);
+
+    if let Ok(bytes) = fs::read(&path) {
+        if let Ok(cached) = bincode::deserialize::<Vec<(String, String)>>(&bytes) {
+            return cached;
+        }
+    }
+
+    let entries = build_index_entries(locations);
+    if let Ok(bytes) = bincode::serialize(&entries) {
+        let _ = fs::write(&path, bytes);
+    }
+    entries
+}
+
+/// Ranks an `(name, path)` index against `query` by exact name match,
+/// then case-insensitive prefix match, then bounded edit distance, and
+/// returns the matching paths sorted best-first.
+fn rank_by_name<'a>(query: &str, index: &'a [(String, String)]) -> Vec<&'a str> {
+    let query_lower = query.to_lowercase();
+
+    let mut ranked: Vec<(&'a str, MatchRank)> = index.iter().filter_map(|&(ref name, ref path)| {
+        let name_lower = name.to_lowercase();
+
+        let rank = if name == query {
+            MatchRank::Exact
+        } else if name_lower.starts_with(&query_lower) {
+            MatchRank::Prefix
+        } else {
+            MatchRank::Fuzzy(bounded_levenshtein(&name_lower, &query_lower, FUZZY_DISTANCE_CAP)?)
+        };
+
+        Some((path.as_str(), rank))
+    }).collect();
+
+    ranked.sort_by(|a, b| a.1.cmp(&b.1));
+    ranked.into_iter().map(|(path, _)| path).collect()
+}
+
+/// Resolves indexed path strings back to the live `StoreLocation`s that
+/// produced them, preserving `paths`' order.
+fn resolve_paths<'a>(paths: &[&str], locations: &[&'a StoreLocation]) -> Vec<&'a StoreLocation> {
+    paths.iter().filter_map(|&path| {
+        locations.iter().find(|&&location| location.to_string() == path).cloned()
+    }).collect()
+}
+
+/// A query for a function's shape, parsed from e.g. `"Vec<T> -> usize"`
+/// or `"&str -> Option<T>"`.
+struct TypeQuery {
+    params: Vec<String>,
+    ret: Option<String>,
+}
+
+/// Normalizes a type token for comparison by lowercasing it, so matching
+/// is spelling-insensitive about case. Whether a token is a generic
+/// wildcard is decided separately by `is_generic_wildcard`/`types_match`,
+/// which also have to see inside compound generics like `Vec<T>`.
+fn normalize_type_token(token: &str) -> String {
+    token.trim().to_lowercase()
+}
+
+/// True if a (already-lowercased) type token is a single letter, which
+/// stands for a generic parameter (the `T` in `fn foo<T>(x: T) -> T`) and
+/// so should match any other type.
+fn is_generic_wildcard(token: &str) -> bool {
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => c.is_alphabetic(),
+        _ => false,
+    }
+}
+
+/// Splits a compound generic type like `"vec<t>"` into its outer name and
+/// argument list (`"vec"`, `["t"]`), or `None` if `ty` isn't of that form.
+fn generic_parts(ty: &str) -> Option<(&str, Vec<&str>)> {
+    let open = ty.find('<')?;
+    if !ty.ends_with('>') {
+        return None;
+    }
+    let name = &ty[..open];
+    let inner = &ty[open + 1..ty.len() - 1];
+    Some((name, inner.split(',').map(|s| s.trim()).collect()))
+}
+
+/// Whether two normalized type tokens should count as a match: equal
+/// tokens always match, a bare single-letter token matches anything, and
+/// for compound generics the outer name must match with each argument
+/// compared the same way -- so `Vec<T>` matches both `Vec<U>` (wildcard
+/// argument) and `Vec<usize>` (concrete argument), but not `HashMap<T>`.
+fn types_match(a: &str, b: &str) -> bool {
+    if a == b || is_generic_wildcard(a) || is_generic_wildcard(b) {
+        return true;
+    }
+
+    match (generic_parts(a), generic_parts(b)) {
+        (Some((a_name, a_args)), Some((b_name, b_args))) => {
+            a_name == b_name
+                && a_args.len() == b_args.len()
+                && a_args.iter().zip(b_args.iter()).all(|(x, y)| types_match(x, y))
+        }
+        _ => false,
+    }
+}
+
+/// Splits a parameter list on top-level commas only, so a multi-argument
+/// generic like `"HashMap<K, V>"` stays one token instead of being torn
+/// apart at the comma inside its angle brackets.
+fn split_top_level(params: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0usize;
+
+    for (i, c) in params.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&params[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&params[start..]);
+    parts
+}
+
+fn split_params(params: &str) -> Vec<String> {
+    split_top_level(params.trim().trim_start_matches('(').trim_end_matches(')'))
+        .into_iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(normalize_type_token)
+        .collect()
+}
+
+/// Parses a query into normalized parameter/return slots if it looks like
+/// a type signature (contains `->`); otherwise returns `None` so the
+/// caller can fall back to name search.
+fn parse_type_query(query: &str) -> Option<TypeQuery> {
+    if !query.contains("->") {
+        return None;
+    }
+
+    let idx = query.find("->").unwrap();
+    let params = split_params(&query[..idx]);
+    let ret = normalize_type_token(&query[idx + 2..]);
+
+    Some(TypeQuery { params: params, ret: Some(ret) })
+}
+
+/// Pulls normalized parameter/return type slots out of a function-header
+/// string like `"(name: &str, other: T) -> Option<U>"`, dropping the
+/// argument names so only the types are compared.
+fn extract_header_types(header: &str) -> (Vec<String>, Option<String>) {
+    let header = header.trim();
+    let (args_part, ret_part) = match header.find("->") {
+        Some(idx) => (&header[..idx], Some(header[idx + 2..].trim())),
+        None => (header, None),
+    };
+
+    let params = split_params(args_part)
+        .into_iter()
+        .map(|arg| match arg.rfind(':') {
+            Some(idx) => normalize_type_token(&arg[idx + 1..]),
+            None => arg,
+        })
+        .collect();
+
+    (params, ret_part.map(normalize_type_token))
+}
+
+/// Scores how many of `query`'s parameter/return slots are satisfied by
+/// a candidate header, treating the parameters as a multiset (order
+/// doesn't matter) and matching types via `types_match` so single-letter
+/// and compound-generic wildcards both apply.
+fn type_query_score(query: &TypeQuery, header_params: &[String], header_ret: &Option<String>) -> usize {
+    let mut remaining = header_params.to_vec();
+    let mut score = 0;
+
+    for param in &query.params {
+        let found = remaining.iter().position(|h| types_match(h, param));
+        if let Some(pos) = found {
+            remaining.remove(pos);
+            score += 1;
+        }
+    }
+
+    if let (&Some(ref q_ret), &Some(ref h_ret)) = (&query.ret, header_ret) {
+        if types_match(q_ret, h_ret) {
+            score += 1;
+        }
+    }
+
+    score
+}
+
+fn fn_header(data: &Documentation) -> Option<String> {
+    match data.inner_data {
+        DocInnerData::FnDoc(ref func) => Some(func.header.to_string()),
+        DocInnerData::TraitItemDoc(ref item) => match item.node {
+            TraitItemKind::Method(ref sig) => Some(sig.header.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Searches by approximate type signature rather than by name, ranking
+/// candidates by how many parameter/return slots of `query` they satisfy.
+fn rank_by_type<'a>(query: &TypeQuery, locations: &[&'a StoreLocation]) -> Vec<&'a StoreLocation> {
+    let mut scored: Vec<(&StoreLocation, usize)> = locations.iter().filter_map(|&location| {
+        let doc = Driver::get_doc(location).ok()?;
+        let header = fn_header(&doc)?;
+        let (params, ret) = extract_header_types(&header);
+        let score = type_query_score(query, &params, &ret);
+        if score > 0 { Some((location, score)) } else { None }
+    }).collect();
+
+    scored.sort_by(|a, b| b.1.cmp(&a.1));
+    scored.into_iter().map(|(location, _)| location).collect()
+}
+
+fn page_search_query(query: &str, format: OutputFormat) -> Result<()> {
     let store = Store::load();
-    // search::add_search_paths(store.all_locations());
+    let all_locations = store.all_locations();
 
-    let results: Vec<&StoreLocation> = store.lookup_name(query).into_iter().take(10).collect();
+    let results = match parse_type_query(query) {
+        Some(type_query) => rank_by_type(&type_query, &all_locations),
+        None => {
+            let index = load_or_build_index(&all_locations);
+            let ranked_paths = rank_by_name(query, &index);
+            resolve_paths(&ranked_paths, &all_locations)
+        }
+    };
 
     if results.is_empty() {
-        println!("No results for \"{}\".", query);
+        match format {
+            OutputFormat::Json => println!("{{\"results\":[]}}"),
+            OutputFormat::Html => println!("<p>No results for \"{}\".</p>", markup::html_escape(query)),
+            OutputFormat::Ansi | OutputFormat::Markdown => println!("No results for \"{}\".", query),
+        }
         return Ok(());
     }
 
-    let formatted: Vec<String> = results.into_iter().map(|location| {
+    let formatted: Vec<String> = results.into_iter().take(10).map(|location| {
         let result = Driver::get_doc(&location).unwrap();
+        let body = result.format().render(format);
 
-        result.format().to_string()
+        // Ansi/Markdown read fine with the location on its own line above
+        // the doc; Json/Html are meant to be machine-parsed, so the
+        // location has to live inside the structured payload instead of
+        // as a raw line in front of it.
+        match format {
+            OutputFormat::Json => format!(
+                "{{\"location\":\"{}\",\"doc\":{}}}",
+                markup::json_escape(&location.to_string()),
+                body
+            ),
+            OutputFormat::Html => format!(
+                "<h3>{}</h3>\n{}",
+                markup::html_escape(&location.to_string()),
+                body
+            ),
+            OutputFormat::Ansi | OutputFormat::Markdown => format!("{}\n{}", location, body),
+        }
     }).collect();
 
-    setup_pager();
+    // Only page plain terminal output; structured formats are meant to be
+    // piped into other tools, not read interactively.
+    if format == OutputFormat::Ansi {
+        setup_pager();
+    }
 
     for result in formatted {
         println!("{}", result);
@@ -1,10 +1,15 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
 
 use ansi_term::Style;
 use catmark::{self, OutputKind};
 use conversion::*;
 use document::ModPath;
+use driver::Driver;
 use generation::ast_ty_wrappers::{FnKind, Attributes};
+use regex::{Captures, Regex};
+use store::Store;
 use term_size;
 
 pub enum Markup {
@@ -18,6 +23,33 @@ pub enum Markup {
 
 use self::Markup::*;
 
+/// Backend that a `Markup`/`MarkupDoc` tree is rendered to.
+///
+/// `Markup` itself only describes document structure (headers, rules,
+/// prose); picking how that structure turns into bytes is the job of
+/// `OutputFormat`, so the same doc model can feed a terminal pager, an
+/// HTML page, or a machine-readable dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Colored text for a terminal/pager (the historical default).
+    Ansi,
+    Html,
+    Markdown,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_str(s: &str) -> Option<OutputFormat> {
+        match s {
+            "ansi" | "text" | "term" => Some(OutputFormat::Ansi),
+            "html" => Some(OutputFormat::Html),
+            "markdown" | "md" => Some(OutputFormat::Markdown),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
 fn get_term_width() -> u16 {
     match term_size::dimensions() {
         Some((w, _)) => w as u16,
@@ -25,6 +57,121 @@ fn get_term_width() -> u16 {
     }
 }
 
+/// Escapes `text` for embedding in an HTML backend render. Exposed so
+/// callers assembling their own HTML around a rendered `MarkupDoc` (e.g.
+/// to label a search result) can stay consistent with how `Markup`
+/// itself escapes text.
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes `text` for embedding in a JSON backend render. Exposed for the
+/// same reason as `html_escape`.
+pub fn json_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Resolves `[Target]`/`[crate::module::func]`-style intra-doc links in
+/// `md` against the `Store`, rewriting each resolvable target to show
+/// the canonical `ModPath` of the matched item. Unresolved links are
+/// left untouched. When `as_html` is set, resolved links become actual
+/// `<a>` anchors instead of plain `[ModPath]` text.
+///
+/// `[Target](url)` and `[Target][ref]` are ordinary inline/reference
+/// markdown links, not intra-doc links, even when `Target` happens to
+/// resolve in the store -- the regex crate has no negative lookahead, so
+/// a trailing `(` or `[` is captured as part of the match and the whole
+/// thing is left untouched instead of being rewritten.
+fn resolve_intra_doc_links(md: &str, as_html: bool) -> String {
+    let store = Store::load();
+    let re = intra_doc_link_regex();
+
+    re.replace_all(md, |caps: &Captures| {
+        if caps.get(2).is_some() {
+            return caps[0].to_string();
+        }
+
+        let target = &caps[1];
+        match store.lookup_name(target).into_iter().next() {
+            Some(location) => {
+                let path = location.to_string();
+                if as_html {
+                    format!("<a href=\"#{}\">{}</a>", html_escape(&path), html_escape(&path))
+                } else {
+                    format!("[{}]", path)
+                }
+            }
+            None => caps[0].to_string(),
+        }
+    }).into_owned()
+}
+
+fn intra_doc_link_regex() -> Regex {
+    Regex::new(r"\[([A-Za-z_][A-Za-z0-9_:]*)\](\(|\[)?").unwrap()
+}
+
+impl Markup {
+    /// Renders this piece of markup through the given backend.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Ansi => self.to_string(),
+            OutputFormat::Html => self.render_html(),
+            OutputFormat::Markdown => self.render_markdown(),
+            OutputFormat::Json => self.render_json(),
+        }
+    }
+
+    fn render_html(&self) -> String {
+        match *self {
+            Header(ref text) => format!("<h1>{}</h1>", html_escape(text)),
+            Section(ref text) => format!("<h2>{}</h2>", html_escape(text)),
+            Block(ref text) => format!("<p>{}</p>", html_escape(text)),
+            Markdown(ref md) => format!("<div class=\"markdown\">{}</div>", resolve_intra_doc_links(md, true)),
+            Rule(_) => "<hr>".to_string(),
+            LineBreak => "<br>".to_string(),
+        }
+    }
+
+    fn render_markdown(&self) -> String {
+        match *self {
+            Header(ref text) => format!("# {}", text),
+            Section(ref text) => format!("## {}", text),
+            Block(ref text) => text.clone(),
+            Markdown(ref md) => resolve_intra_doc_links(md, false),
+            Rule(ref count) => "-".repeat(*count),
+            LineBreak => "".to_string(),
+        }
+    }
+
+    fn render_json(&self) -> String {
+        match *self {
+            Header(ref text) => format!("{{\"type\":\"header\",\"text\":\"{}\"}}", json_escape(text)),
+            Section(ref text) => format!("{{\"type\":\"section\",\"text\":\"{}\"}}", json_escape(text)),
+            Block(ref text) => format!("{{\"type\":\"block\",\"text\":\"{}\"}}", json_escape(text)),
+            Markdown(ref md) => format!(
+                "{{\"type\":\"markdown\",\"text\":\"{}\"}}",
+                json_escape(&resolve_intra_doc_links(md, false))
+            ),
+            Rule(ref count) => format!("{{\"type\":\"rule\",\"width\":{}}}", count),
+            LineBreak => "{\"type\":\"line_break\"}".to_string(),
+        }
+    }
+}
+
 impl fmt::Display for Markup {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let string = match *self {
@@ -43,7 +190,7 @@ impl fmt::Display for Markup {
             Block(ref text) => text.clone(),
             Markdown(ref md) => {
                 let width = get_term_width();
-                catmark::render_ansi(md, width, OutputKind::Color)
+                catmark::render_ansi(&resolve_intra_doc_links(md, false), width, OutputKind::Color)
             }
             Rule(ref count) => "-".repeat(*count),
             LineBreak => "".to_string(),
@@ -61,6 +208,20 @@ impl MarkupDoc {
     pub fn new(parts: Vec<Markup>) -> Self {
         MarkupDoc { parts: parts }
     }
+
+    /// Renders the whole document through the given backend.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Json => {
+                let parts: Vec<String> = self.parts.iter().map(|part| part.render(format)).collect();
+                format!("[{}]", parts.join(","))
+            }
+            _ => {
+                let parts: Vec<String> = self.parts.iter().map(|part| part.render(format)).collect();
+                parts.join("\n")
+            }
+        }
+    }
 }
 
 impl fmt::Display for MarkupDoc {
@@ -132,8 +293,77 @@ fn doc_body(data: &Documentation) -> MarkupDoc {
     data.attrs.format()
 }
 
+thread_local! {
+    /// Maps a type's `ModPath` string to the methods hanging off its impl
+    /// blocks, built once per process on first use and reused for every
+    /// later page -- see `methods_of`.
+    static METHOD_INDEX: RefCell<Option<HashMap<String, Vec<String>>>> = RefCell::new(None);
+}
+
+/// Builds the full parent-path -> methods map in a single pass over
+/// `store.all_locations()`, rather than rescanning the whole store for
+/// every page: `doc_related_items` is called once per rendered item, and
+/// without this the scan cost would grow linearly with registry size on
+/// every single doc view.
+fn build_method_index() -> HashMap<String, Vec<String>> {
+    let store = Store::load();
+    let mut index: HashMap<String, Vec<String>> = HashMap::new();
+
+    for location in store.all_locations() {
+        let doc = match Driver::get_doc(location) {
+            Ok(doc) => doc,
+            Err(_) => continue,
+        };
+
+        if let DocInnerData::FnDoc(ref func) = doc.inner_data {
+            if let FnKind::MethodFromImpl = func.kind {
+                if let Some(parent) = doc.mod_path.parent() {
+                    index.entry(parent.to_string()).or_insert_with(Vec::new).push(doc.mod_path.to_string());
+                }
+            }
+        }
+    }
+
+    index
+}
+
+fn methods_of(target_path: &str) -> Vec<String> {
+    METHOD_INDEX.with(|cell| {
+        if cell.borrow().is_none() {
+            *cell.borrow_mut() = Some(build_method_index());
+        }
+        cell.borrow().as_ref().unwrap().get(target_path).cloned().unwrap_or_default()
+    })
+}
+
+/// Lists the methods attached to this type, the way rustdoc pre-populates
+/// its `Cache` before rendering a page, via `methods_of`'s cached
+/// parent-path index rather than a `Store::related_items` method that
+/// doesn't exist here.
+///
+/// Trait Implementations/Implementors sections are left out: `FnKind`
+/// only distinguishes a method that came from an impl block from one
+/// that didn't, it doesn't say *which* trait (if any) that impl is for,
+/// and there's no reverse index here from a trait to the types that
+/// implement it. Recovering either needs the trait resolution the
+/// generator's AST walk would have to record at generation time; there's
+/// nothing in a `Documentation`/`StoreLocation` to reconstruct it from at
+/// render time.
 fn doc_related_items(data: &Documentation) -> MarkupDoc {
-    MarkupDoc::new(vec![])
+    let target_path = data.mod_path.to_string();
+    let methods = methods_of(&target_path);
+
+    if methods.is_empty() {
+        return MarkupDoc::new(vec![]);
+    }
+
+    let mut parts = vec![Section("Methods".to_string())];
+    for method in &methods {
+        parts.push(Block(format!("  fn {}", method)));
+    }
+    parts.push(LineBreak);
+
+    MarkupDoc::new(parts)
 }
 
 fn doc_inner_info(data: &Documentation) -> MarkupDoc {